@@ -1,10 +1,52 @@
 #![allow(dead_code)]
 
-use std::{cell::RefCell, collections::HashSet, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    io::{self, Read, Write},
+    rc::Rc,
+};
 
 const MB: u64 = 1_048_576;
 const BATCH_SIZE: u64 = 10 * MB;
 
+/// Writes `value` as a LEB128 varint: 7 bits of payload per byte, continuation in the MSB.
+fn write_varint<W: Write>(w: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        w.write_all(&[byte])?;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a LEB128 varint written by [`write_varint`].
+fn read_varint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    Ok(value)
+}
+
 struct Batch {
     current: u64,
     size: u64,
@@ -35,6 +77,9 @@ impl Batch {
     }
 }
 
+/// The number of candidates packed into a single bitset word.
+const WORD_BITS: u64 = u64::BITS as u64;
+
 /// The Primes struct is responsible to find and save all the primes (so far).
 /// Its design uses batches to calculate the next set of primes with the previus ones.
 ///
@@ -48,7 +93,9 @@ impl Batch {
 /// assert!(primes.is_prime(5));
 /// ```
 pub struct Primes {
-    inner_slice: Box<Vec<bool>>,
+    /// Bitset over the odd numbers in the current batch: bit `i` represents the odd number
+    /// `batch.offset + 2*i + 1`. The prime 2 is handled separately since it has no slot here.
+    inner_slice: Box<Vec<u64>>,
     batch: Batch,
     pub primes_found: Rc<RefCell<HashSet<u64>>>,
     pub primes_ordered: Vec<u64>,
@@ -60,12 +107,26 @@ impl Primes {
     }
 
     pub fn with_batch_size(batch_size: u64) -> Self {
+        assert!(
+            batch_size.is_multiple_of(2),
+            "batch_size must be even: the odd-only bitset indexes bit i as offset + 2*i + 1, \
+             which requires every batch offset (a multiple of batch_size) to be even"
+        );
+
+        let bits = batch_size / 2;
+        let words = bits.div_ceil(WORD_BITS) as usize;
+
         let mut s = Self {
-            inner_slice: Box::new(vec![true; batch_size as usize]),
+            inner_slice: Box::new(vec![u64::MAX; words]),
             primes_found: Rc::new(RefCell::new(HashSet::new())),
             primes_ordered: Vec::new(),
             batch: Batch::new(batch_size),
         };
+
+        // 2 is the only even prime, so it has no bit of its own in the odd-only bitset.
+        s.primes_found.borrow_mut().insert(2);
+        s.primes_ordered.push(2);
+
         s.populate_first_batch();
         s.save_primes();
 
@@ -79,33 +140,40 @@ impl Primes {
         }
     }
 
+    /// Returns whether bit `i` (the odd number `offset + 2*i + 1`) is still marked prime.
+    fn bit_is_set(&self, i: usize) -> bool {
+        (self.inner_slice[i / WORD_BITS as usize] >> (i as u64 % WORD_BITS)) & 1 == 1
+    }
+
+    /// Marks bit `i` (the odd number `offset + 2*i + 1`) as composite.
+    fn bit_clear(&mut self, i: usize) {
+        self.inner_slice[i / WORD_BITS as usize] &= !(1u64 << (i as u64 % WORD_BITS));
+    }
+
     fn populate_first_batch(&mut self) {
-        self.inner_slice[0] = false;
-        self.inner_slice[1] = false;
-        for i in 2..self.inner_slice.len() {
-            if self.inner_slice[i] {
-                let mut tmp = i + i;
-                while tmp < self.batch.size as usize {
-                    self.inner_slice[tmp] = false;
-
-                    tmp += i;
+        self.bit_clear(0); // 1 is not prime
+
+        let bits = (self.batch.size / 2) as usize;
+        for i in 1..bits {
+            if self.bit_is_set(i) {
+                let m = 2 * i as u64 + 1;
+                let mut tmp = m * m;
+
+                while tmp < self.batch.size {
+                    let idx = (tmp - 1) / 2;
+                    self.bit_clear(idx as usize);
+
+                    tmp += 2 * m;
                 }
             }
         }
     }
 
     fn save_primes(&mut self) {
-        let primes: Vec<u64> = self
-            .inner_slice
-            .iter()
-            .enumerate()
-            .filter_map(|(i, is_prime)| {
-                if *is_prime {
-                    Some(i as u64 + self.batch.offset)
-                } else {
-                    None
-                }
-            })
+        let bits = (self.batch.size / 2) as usize;
+        let primes: Vec<u64> = (0..bits)
+            .filter(|&i| self.bit_is_set(i))
+            .map(|i| self.batch.offset + 2 * i as u64 + 1)
             .collect();
 
         self.primes_found.borrow_mut().extend(&primes);
@@ -115,17 +183,36 @@ impl Primes {
     /// This function will calculate and populate the next batch of primes by the specified batch size.
     pub fn populate_next_batch(&mut self) {
         self.batch.update_batch(|current| current + 1);
-        self.inner_slice.fill(true); // Reset the slice to all trues
+        self.inner_slice.fill(u64::MAX); // Reset the bitset to all trues
+
+        let segment_end = self.batch.offset + self.batch.size;
 
         for prime in self.primes_ordered.iter() {
+            if *prime == 2 {
+                continue; // 2 has no bit in the odd-only bitset
+            }
+
+            // The smallest prime factor of any composite <= N is <= sqrt(N), so once
+            // prime*prime reaches the end of this segment, no larger prime can cross it.
+            if prime * prime >= segment_end {
+                break;
+            }
+
             let mul = (self.batch.offset as f64 / *prime as f64).ceil() as u64; // How much to multiply prime to reach offset (closest)
-            let mut tmp = prime * mul;
+            // Multiples below prime^2 were already eliminated by smaller primes, so there's
+            // no need to re-cross them here.
+            let mut tmp = (prime * mul).max(prime * prime);
+            if tmp % 2 == 0 {
+                // Only odd multiples of an odd prime have a bit in the bitset.
+                tmp += prime;
+            }
 
-            while tmp < self.batch.offset + self.batch.size {
-                let indx = tmp - self.batch.offset;
-                self.inner_slice[indx as usize] = false;
+            while tmp < segment_end {
+                let idx = ((tmp - self.batch.offset - 1) / 2) as usize;
+                self.inner_slice[idx / WORD_BITS as usize] &=
+                    !(1u64 << (idx as u64 % WORD_BITS));
 
-                tmp += *prime;
+                tmp += 2 * prime;
             }
         }
 
@@ -136,7 +223,7 @@ impl Primes {
     ///
     /// NOTE: If the prime isn't yet checked, it will calculate the batches until it.
     pub fn is_prime(&mut self, n: u64) -> bool {
-        while n > (self.batch.current + 1) * BATCH_SIZE {
+        while n > (self.batch.current + 1) * self.batch.size {
             self.populate_next_batch();
         }
 
@@ -146,6 +233,131 @@ impl Primes {
     pub fn primes_found_set(&self) -> Rc<RefCell<HashSet<u64>>> {
         self.primes_found.clone()
     }
+
+    /// Returns the prime factorization of `n` as ascending `(prime, exponent)` pairs.
+    ///
+    /// NOTE: This will populate batches on demand, reusing `primes_ordered` instead of
+    /// re-deriving primes from scratch.
+    pub fn factorize(&mut self, mut n: u64) -> Vec<(u64, u32)> {
+        let mut factors = Vec::new();
+        let mut i = 0;
+
+        while n > 1 {
+            while i >= self.primes_ordered.len() {
+                self.populate_next_batch();
+            }
+            let prime = self.primes_ordered[i];
+
+            if prime * prime > n {
+                break;
+            }
+
+            if n.is_multiple_of(prime) {
+                let mut exponent = 0;
+                while n.is_multiple_of(prime) {
+                    n /= prime;
+                    exponent += 1;
+                }
+                factors.push((prime, exponent));
+            }
+
+            i += 1;
+        }
+
+        if n > 1 {
+            factors.push((n, 1));
+        }
+
+        factors
+    }
+
+    /// Returns the number of divisors of `n`, computed as `∏ (e_i + 1)` over its
+    /// prime factorization.
+    pub fn divisor_count(&mut self, n: u64) -> u64 {
+        self.factorize(n)
+            .iter()
+            .map(|(_, exponent)| (*exponent as u64) + 1)
+            .product()
+    }
+
+    /// Returns the `k`-th prime (0-indexed).
+    ///
+    /// NOTE: If the prime isn't yet computed, it will calculate the batches until it.
+    pub fn nth_prime(&mut self, k: usize) -> u64 {
+        while self.primes_ordered.len() <= k {
+            self.populate_next_batch();
+        }
+
+        self.primes_ordered[k]
+    }
+
+    /// Returns the count of primes less than or equal to `n` (the prime-counting function π(n)).
+    pub fn prime_pi(&mut self, n: u64) -> usize {
+        while n > (self.batch.current + 1) * self.batch.size {
+            self.populate_next_batch();
+        }
+
+        match self.primes_ordered.binary_search(&n) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        }
+    }
+
+    /// Serializes the discovered primes and the current batch position to `w`, so a future
+    /// [`load_from`](Primes::load_from) can continue sieving without starting over.
+    ///
+    /// Primes are delta-encoded as varints (gaps between consecutive primes are small), which
+    /// keeps the stream far smaller than a raw dump of `u64`s.
+    pub fn save_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_varint(w, self.primes_ordered.len() as u64)?;
+        write_varint(w, self.batch.size)?;
+        write_varint(w, self.batch.current)?;
+
+        let mut prev = 0u64;
+        for &prime in &self.primes_ordered {
+            write_varint(w, prime - prev)?;
+            prev = prime;
+        }
+
+        Ok(())
+    }
+
+    /// Reconstructs a `Primes` from a stream written by [`save_to`](Primes::save_to), ready to
+    /// resume sieving from where serialization left off.
+    pub fn load_from<R: Read>(r: &mut R) -> io::Result<Primes> {
+        let count = read_varint(r)? as usize;
+        let batch_size = read_varint(r)?;
+        let current = read_varint(r)?;
+
+        if !batch_size.is_multiple_of(2) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "batch_size must be even: the odd-only bitset requires every batch offset to be even",
+            ));
+        }
+
+        let mut primes_ordered = Vec::with_capacity(count);
+        let mut prev = 0u64;
+        for _ in 0..count {
+            prev += read_varint(r)?;
+            primes_ordered.push(prev);
+        }
+
+        let primes_found = Rc::new(RefCell::new(primes_ordered.iter().copied().collect()));
+
+        let bits = batch_size / 2;
+        let words = bits.div_ceil(WORD_BITS) as usize;
+
+        let mut batch = Batch::new(batch_size);
+        batch.set_batch(current);
+
+        Ok(Primes {
+            inner_slice: Box::new(vec![u64::MAX; words]),
+            batch,
+            primes_found,
+            primes_ordered,
+        })
+    }
 }
 
 /// Struct to iterate over all the primes until `u64::MAX`.
@@ -218,4 +430,42 @@ mod tests {
             p.iter().take(primes_vec.len()).collect::<Vec<u64>>()
         )
     }
+
+    #[test]
+    fn test_factorize_and_divisor_count() {
+        let mut p = Primes::with_batch_size(BATCH_SIZE);
+
+        assert_eq!(p.factorize(1), vec![]);
+        assert_eq!(p.factorize(2), vec![(2, 1)]);
+        assert_eq!(p.factorize(60), vec![(2, 2), (3, 1), (5, 1)]);
+        assert_eq!(p.factorize(9), vec![(3, 2)]); // exact p*p == n boundary
+        assert_eq!(p.factorize(1_299_709), vec![(1_299_709, 1)]); // large prime remainder
+
+        assert_eq!(p.divisor_count(1), 1);
+        assert_eq!(p.divisor_count(60), 12);
+        assert_eq!(p.divisor_count(1_299_709), 2);
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let mut p = Primes::with_batch_size(64);
+        p.populate_next_batch();
+        p.populate_next_batch();
+
+        let mut buf = Vec::new();
+        p.save_to(&mut buf).unwrap();
+
+        let mut loaded = Primes::load_from(&mut &buf[..]).unwrap();
+        assert_eq!(p.primes_ordered, loaded.primes_ordered);
+
+        // A custom, persisted batch size must still drive correct batch-advance math.
+        assert_eq!(loaded.prime_pi(1000), 168);
+        assert!(loaded.is_prime(997));
+        assert!(!loaded.is_prime(1000));
+
+        // Sieving can continue seamlessly from where serialization left off.
+        loaded.populate_next_batch();
+        assert!(loaded.primes_ordered.len() > p.primes_ordered.len());
+        assert_eq!(loaded.primes_ordered[..p.primes_ordered.len()], p.primes_ordered[..]);
+    }
 }